@@ -0,0 +1,137 @@
+//! Dynamic DNS support: watch the router's WANIP and push changes to a DNS provider.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+use governor::{DefaultDirectRateLimiter, Quota};
+use rand::Rng;
+use reqwest::Client;
+use serde::Serialize;
+
+use crate::{Result, Tianyi};
+
+/// Pushes WANIP changes to a DNS provider.
+///
+/// Implement this trait to support a provider other than [`RestDnsUpdater`].
+#[async_trait::async_trait]
+pub trait DnsUpdater {
+    /// Updates the provider's A/AAAA records. Either address may be `None` if the router
+    /// did not report one.
+    async fn update(&self, ipv4: Option<Ipv4Addr>, ipv6: Option<Ipv6Addr>) -> Result<()>;
+}
+
+#[derive(Debug, Serialize)]
+struct DnsRecordUpdate<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ipv4: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ipv6: Option<String>,
+    ttl: u32,
+}
+
+/// A [`DnsUpdater`] that PUTs the record to a REST DNS API, authenticating with an API key.
+pub struct RestDnsUpdater {
+    client: Client,
+    endpoint: String,
+    api_key: String,
+    record_name: String,
+    ttl: u32,
+}
+
+impl RestDnsUpdater {
+    /// Creates an updater that sends updates for `record_name` to `endpoint`.
+    pub fn new(
+        client: Client,
+        endpoint: impl Into<String>,
+        api_key: impl Into<String>,
+        record_name: impl Into<String>,
+        ttl: u32,
+    ) -> Self {
+        Self {
+            client,
+            endpoint: endpoint.into(),
+            api_key: api_key.into(),
+            record_name: record_name.into(),
+            ttl,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl DnsUpdater for RestDnsUpdater {
+    async fn update(&self, ipv4: Option<Ipv4Addr>, ipv6: Option<Ipv6Addr>) -> Result<()> {
+        let body = DnsRecordUpdate {
+            name: &self.record_name,
+            ipv4: ipv4.map(|ip| ip.to_string()),
+            ipv6: ipv6.map(|ip| ip.to_string()),
+            ttl: self.ttl,
+        };
+
+        self.client
+            .put(&self.endpoint)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+impl Tianyi {
+    /// Polls the gateway every `interval` and pushes the WANIP to `updater` whenever it
+    /// changes, caching the last-seen addresses so unchanged polls are a no-op.
+    ///
+    /// Runs until the caller drops/aborts the enclosing task. Both the poll and the update
+    /// are gated behind a rate limiter so a flapping WANIP can't hammer the router or the
+    /// DNS provider; the limiter's quota is derived from `interval` itself (plus a small
+    /// burst), so it paces polling rather than imposing a separate, fixed rate.
+    pub async fn watch_wanip<U: DnsUpdater>(&self, interval: Duration, updater: U) -> Result<()> {
+        let quota = Quota::with_period(interval)
+            .unwrap_or_else(|| Quota::per_minute(NonZeroU32::new(6).unwrap()))
+            .allow_burst(NonZeroU32::new(2).unwrap());
+        let limiter = DefaultDirectRateLimiter::direct(quota);
+
+        let mut last_ipv4: Option<Ipv4Addr> = None;
+        let mut last_ipv6: Option<Ipv6Addr> = None;
+
+        loop {
+            Self::wait_for_slot(&limiter).await;
+
+            let info = match self.gwinfo().await {
+                Ok(info) => info,
+                Err(err) => {
+                    eprintln!("watch_wanip: gwinfo poll failed, will retry: {err}");
+                    tokio::time::sleep(interval).await;
+                    continue;
+                }
+            };
+            let ipv4 = info.wan_ip.parse::<Ipv4Addr>().ok();
+            let ipv6 = info.wan_ipv6.parse::<Ipv6Addr>().ok();
+
+            if ipv4 != last_ipv4 || ipv6 != last_ipv6 {
+                match updater.update(ipv4, ipv6).await {
+                    Ok(()) => {
+                        last_ipv4 = ipv4;
+                        last_ipv6 = ipv6;
+                    }
+                    Err(err) => {
+                        eprintln!("watch_wanip: DNS update failed, will retry next poll: {err}");
+                    }
+                }
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn wait_for_slot(limiter: &DefaultDirectRateLimiter) {
+        while limiter.check().is_err() {
+            let jitter = Duration::from_millis(rand::thread_rng().gen_range(50..250));
+            tokio::time::sleep(jitter).await;
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Error types returned by the `tianyi_api` library.
+
+/// Errors that can occur while talking to a Tianyi router.
+#[derive(Debug, thiserror::Error)]
+pub enum TianyiError {
+    /// The router rejected the login request itself (e.g. bad credentials), rather than
+    /// responding successfully with an unparseable body.
+    #[error("login failed: router rejected the login request")]
+    LoginFailed,
+
+    /// The login response was successful but no session token could be parsed out of it.
+    #[error("failed to parse session token from login response")]
+    TokenParse,
+
+    /// The underlying HTTP request failed.
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    /// The response body could not be deserialized into the expected shape.
+    #[error("failed to deserialize router response: {0}")]
+    Deserialize(#[from] serde_json::Error),
+
+    /// The router rejected an operation, reporting a non-success `retVal`.
+    #[error("router rejected `{op}` with retVal {ret_val}")]
+    RouterRejected { op: String, ret_val: i32 },
+
+    /// A response body that was expected to contain a bare IP address did not.
+    #[error("invalid IP address in response: {0:?}")]
+    InvalidIp(String),
+}
+
+/// A `Result` alias using [`TianyiError`] as the error type.
+pub type Result<T> = std::result::Result<T, TianyiError>;
@@ -0,0 +1,125 @@
+//! Independent public-IP lookups, used to cross-check the router's self-reported WANIP.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use reqwest::Client;
+
+use crate::{Result, Tianyi, TianyiError};
+
+/// A source that can independently report this host's public IP address.
+#[async_trait::async_trait]
+pub trait IpSource {
+    async fn get_ipv4(&self) -> Result<Ipv4Addr>;
+    async fn get_ipv6(&self) -> Result<Ipv6Addr>;
+}
+
+/// An [`IpSource`] backed by a plain-text HTTP echo service (e.g. icanhazip, ipify, seeip)
+/// that returns just the caller's IP address in the response body.
+pub struct HttpEchoSource {
+    client: Client,
+    ipv4_url: String,
+    ipv6_url: String,
+}
+
+impl HttpEchoSource {
+    pub fn new(client: Client, ipv4_url: impl Into<String>, ipv6_url: impl Into<String>) -> Self {
+        Self {
+            client,
+            ipv4_url: ipv4_url.into(),
+            ipv6_url: ipv6_url.into(),
+        }
+    }
+
+    pub fn icanhazip(client: Client) -> Self {
+        Self::new(client, "https://icanhazip.com", "https://ipv6.icanhazip.com")
+    }
+
+    pub fn ipify(client: Client) -> Self {
+        Self::new(client, "https://api.ipify.org", "https://api6.ipify.org")
+    }
+
+    pub fn seeip(client: Client) -> Self {
+        Self::new(client, "https://ip4.seeip.org", "https://ip6.seeip.org")
+    }
+
+    async fn fetch(&self, url: &str) -> Result<String> {
+        let text = self.client.get(url).send().await?.text().await?;
+        Ok(text.trim().to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl IpSource for HttpEchoSource {
+    async fn get_ipv4(&self) -> Result<Ipv4Addr> {
+        let text = self.fetch(&self.ipv4_url).await?;
+        text.parse().map_err(|_| TianyiError::InvalidIp(text))
+    }
+
+    async fn get_ipv6(&self) -> Result<Ipv6Addr> {
+        let text = self.fetch(&self.ipv6_url).await?;
+        text.parse().map_err(|_| TianyiError::InvalidIp(text))
+    }
+}
+
+/// The default, order-tried set of `IpSource`s, sharing `client`'s configured proxy.
+pub fn default_ip_sources(client: Client) -> Vec<Box<dyn IpSource>> {
+    vec![
+        Box::new(HttpEchoSource::icanhazip(client.clone())),
+        Box::new(HttpEchoSource::ipify(client.clone())),
+        Box::new(HttpEchoSource::seeip(client)),
+    ]
+}
+
+/// Result of cross-checking the router's WANIP against one or more external `IpSource`s.
+#[derive(Debug, Clone, Copy)]
+pub struct WanipCheck {
+    pub router_ipv4: Option<Ipv4Addr>,
+    pub router_ipv6: Option<Ipv6Addr>,
+    pub external_ipv4: Option<Ipv4Addr>,
+    pub external_ipv6: Option<Ipv6Addr>,
+    /// `true` if the router's IPv4 matches the externally observed IPv4. `false` here
+    /// (with both addresses present) usually indicates double-NAT / CGNAT.
+    pub agrees: bool,
+}
+
+impl Tianyi {
+    /// Convenience wrapper around [`default_ip_sources`] that shares this instance's
+    /// configured `reqwest::Client` (and therefore its proxy) with the returned sources.
+    pub fn default_ip_sources(&self) -> Vec<Box<dyn IpSource>> {
+        default_ip_sources(self.client())
+    }
+
+    /// Queries the router's own WANIP plus `sources` (tried in order, with fallback on
+    /// failure) and reports whether they agree.
+    pub async fn verified_wanip(&self, sources: &[Box<dyn IpSource>]) -> Result<WanipCheck> {
+        let info = self.gwinfo().await?;
+        let router_ipv4 = info.wan_ip.parse().ok();
+        let router_ipv6 = info.wan_ipv6.parse().ok();
+
+        let mut external_ipv4 = None;
+        for source in sources {
+            if let Ok(ip) = source.get_ipv4().await {
+                external_ipv4 = Some(ip);
+                break;
+            }
+        }
+
+        let mut external_ipv6 = None;
+        for source in sources {
+            if let Ok(ip) = source.get_ipv6().await {
+                external_ipv6 = Some(ip);
+                break;
+            }
+        }
+
+        let agrees = router_ipv4.is_some() && router_ipv4 == external_ipv4;
+
+        Ok(WanipCheck {
+            router_ipv4,
+            router_ipv6,
+            external_ipv4,
+            external_ipv6,
+            agrees,
+        })
+    }
+}
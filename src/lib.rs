@@ -4,8 +4,8 @@
 //! It allows you to perform operations such as obtaining public IP addresses, updating
 //! port forwarding rules, and more.
 //!
-//! This library provides an asynchronous API built with `tokio` and error handling
-//! with `anyhow`. It is designed to be simple to use and efficient.
+//! This library provides an asynchronous API built with `tokio` and typed error handling
+//! via [`TianyiError`]. It is designed to be simple to use and efficient.
 //!
 //! ## Features
 //!
@@ -62,16 +62,71 @@
 //! Use of this library is at your own risk. The authors and contributors are not responsible for any damage or issues that may arise from using this library.
 //!
 use std::collections::HashMap;
+use std::num::NonZeroU32;
 
-use anyhow::{Context, Result};
+use governor::{DefaultDirectRateLimiter, Quota};
 use reqwest::{Client, Proxy};
 use serde::Deserialize;
 use rand::Rng;
+use tokio::sync::RwLock;
+
+mod ddns;
+mod error;
+mod ip_source;
+mod wan_stats;
+pub use ddns::{DnsUpdater, RestDnsUpdater};
+pub use error::{Result, TianyiError};
+pub use ip_source::{default_ip_sources, HttpEchoSource, IpSource, WanipCheck};
+pub use wan_stats::WanStats;
 
 const DEFAULT_IP: &str = "192.168.1.1";
 const DEFAULT_UNAME: &str = "useradmin";
 const DEFAULT_UPWD: &str = "";
 
+/// Proxy configuration used when connecting to the router.
+///
+/// Defaults to [`ProxyConfig::None`]; set via [`TianyiBuilder::proxy`] or
+/// [`TianyiBuilder::no_proxy`].
+#[derive(Debug, Clone)]
+pub enum ProxyConfig {
+    /// Connect to the router directly.
+    None,
+    /// Route requests through an HTTP proxy, e.g. `http://127.0.0.1:8083`.
+    Http(String),
+    /// Route requests through a SOCKS5 proxy, e.g. to reach a router over an SSH tunnel.
+    Socks5 {
+        addr: String,
+        username: Option<String>,
+        password: Option<String>,
+    },
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        ProxyConfig::None
+    }
+}
+
+impl ProxyConfig {
+    fn into_reqwest_proxy(self) -> Result<Option<Proxy>> {
+        match self {
+            ProxyConfig::None => Ok(None),
+            ProxyConfig::Http(addr) => Ok(Some(Proxy::http(&addr)?)),
+            ProxyConfig::Socks5 {
+                addr,
+                username,
+                password,
+            } => {
+                let mut proxy = Proxy::all(format!("socks5h://{}", addr))?;
+                if let (Some(username), Some(password)) = (username, password) {
+                    proxy = proxy.basic_auth(&username, &password);
+                }
+                Ok(Some(proxy))
+            }
+        }
+    }
+}
+
 /// `TianyiBuilder` is a builder for the `Tianyi` struct.
 ///
 /// This builder allows you to set the router's IP address, username, and password before creating a `Tianyi` instance.
@@ -79,6 +134,7 @@ pub struct TianyiBuilder {
     ip: String,
     username: String,
     password: String,
+    proxy: ProxyConfig,
 }
 
 impl Default for TianyiBuilder {
@@ -87,6 +143,7 @@ impl Default for TianyiBuilder {
             ip: DEFAULT_IP.to_string(),
             username: DEFAULT_UNAME.to_string(),
             password: DEFAULT_UPWD.to_string(),
+            proxy: ProxyConfig::default(),
         }
     }
 }
@@ -111,16 +168,31 @@ impl TianyiBuilder {
         self
     }
 
+    /// Routes requests to the router through the given proxy instead of connecting directly.
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    /// Connects to the router directly, without a proxy. This is the default.
+    pub fn no_proxy(mut self) -> Self {
+        self.proxy = ProxyConfig::None;
+        self
+    }
+
     pub async fn build(self) -> Result<Tianyi> {
-        Tianyi::new(&self.ip, &self.username, &self.password).await
+        Tianyi::new(&self.ip, &self.username, &self.password, self.proxy).await
     }
 }
 
 /// The `Tianyi` struct represents a connection to a Tianyi router and provides methods to interact with it.
 pub struct Tianyi {
     url: String,
-    token: String,
+    username: String,
+    password: String,
+    token: RwLock<String>,
     client: Client,
+    reauth_limiter: DefaultDirectRateLimiter,
 }
 
 /// Represents the gateway information returned by the router.
@@ -203,38 +275,97 @@ impl PortForwardingAction {
 }
 
 impl Tianyi {
+    /// Returns a clone of the `reqwest::Client` used to reach the router, configured with
+    /// whatever [`ProxyConfig`] the instance was built with. `reqwest::Client` is `Arc`-backed,
+    /// so cloning it is cheap; use this to route other requests (e.g. [`IpSource`] lookups)
+    /// through the same proxy/tunnel as router traffic.
+    pub fn client(&self) -> Client {
+        self.client.clone()
+    }
+
     async fn rand_str() -> String {
         let mut rng = rand::thread_rng();
         rng.gen::<f64>().to_string()
     }
 
+    /// Logs in and returns the session token embedded in the post-login page.
+    async fn login(client: &Client, url: &str, username: &str, password: &str) -> Result<String> {
+        let login_payload = [("username", username), ("psd", password)];
+        let response = client.post(&format!("{}/cgi-bin/luci", url))
+            .form(&login_payload)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(TianyiError::LoginFailed);
+        }
+
+        let text = response.text().await?;
+        let re = regex::Regex::new(r"token: '([a-z0-9]{32})'").unwrap();
+        re.captures(&text)
+            .and_then(|captures| captures.get(1))
+            .map(|m| m.as_str().to_string())
+            .ok_or(TianyiError::TokenParse)
+    }
+
     /// Creates a new `Tianyi` instance with the provided `username` and `password`.
     ///
     /// # Errors
     ///
     /// Returns an `Error` if there is a problem connecting to the router or logging in.
-    async fn new(ip: &str, username: &str, password: &str) -> Result<Self> {
+    async fn new(ip: &str, username: &str, password: &str, proxy: ProxyConfig) -> Result<Self> {
         let url = format!("http://{}", ip);
-        let proxy = Proxy::http("http://127.0.0.1:8083")?;
-        let client = Client::builder()
-            .proxy(proxy)
-            .cookie_store(true)
-            .build()?;
-        let login_payload = [("username", username), ("psd", password)];
-        let response = client.post(&format!("{}/cgi-bin/luci", url))
-            .form(&login_payload)
-            .send()
-            .await?;
+        let mut client_builder = Client::builder().cookie_store(true);
+        if let Some(proxy) = proxy.into_reqwest_proxy()? {
+            client_builder = client_builder.proxy(proxy);
+        }
+        let client = client_builder.build()?;
+        let token = Self::login(&client, &url, username, password).await?;
+
+        Ok(Tianyi {
+            url,
+            username: username.to_string(),
+            password: password.to_string(),
+            token: RwLock::new(token),
+            client,
+            reauth_limiter: DefaultDirectRateLimiter::direct(Quota::per_minute(
+                NonZeroU32::new(6).unwrap(),
+            )),
+        })
+    }
 
-        let token = match response.text().await {
-            Ok(text) => {
-                let re = regex::Regex::new(r"token: '([a-z0-9]{32})'").unwrap();
-                re.captures(&text).context("Failed to parse token")?[1].to_string()
-            }
-            Err(err) => return Err(err.into()),
-        };
+    /// Returns `true` if `text` looks like a response that was served instead of the
+    /// requested data because the session has expired: an empty body, or the login page's
+    /// inline token script (the same marker [`Tianyi::login`] parses the token out of).
+    fn needs_reauth(text: &str) -> bool {
+        text.is_empty() || text.contains("token: '")
+    }
+
+    /// Issues a request built by `request`, and transparently re-logs in and retries once if
+    /// the response indicates the session has expired. Re-login attempts are bounded by
+    /// `reauth_limiter` so a persistently-down router doesn't spin.
+    async fn request_with_reauth<F, Fut>(&self, request: F) -> Result<String>
+    where
+        F: Fn(String) -> Fut,
+        Fut: std::future::Future<Output = std::result::Result<reqwest::Response, reqwest::Error>>,
+    {
+        let token = self.token.read().await.clone();
+        let text = request(token).await?.error_for_status()?.text().await?;
+
+        if !Self::needs_reauth(&text) {
+            return Ok(text);
+        }
+
+        self.reauth_limiter.until_ready().await;
+        let new_token = Self::login(&self.client, &self.url, &self.username, &self.password).await?;
+        *self.token.write().await = new_token.clone();
 
-        Ok(Tianyi { url, client, token })
+        request(new_token)
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+            .map_err(Into::into)
     }
 
     /// Logs out from the router.
@@ -243,18 +374,16 @@ impl Tianyi {
     ///
     /// Returns an `Error` if there is a problem connecting to the router.
     pub async fn logout(&self) -> Result<()> {
-        let payload = [("token", &self.token), ("_", &Self::rand_str().await)];
+        let token = self.token.read().await.clone();
+        let payload = [("token", token.as_str()), ("_", &Self::rand_str().await)];
 
-        let response = self.client.post(&format!("{}/cgi-bin/luci/admin/logout", self.url))
+        self.client.post(&format!("{}/cgi-bin/luci/admin/logout", self.url))
             .form(&payload)
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Failed to logout"))
-        }
+        Ok(())
     }
 
     /// Retrieves gateway information from the router.
@@ -263,15 +392,19 @@ impl Tianyi {
     ///
     /// Returns an `Error` if there is a problem connecting to the router or parsing the response.
     pub async fn gwinfo(&self) -> Result<GatewayInfo> {
-        let payload = [("get", "part"), ("_", &Self::rand_str().await)];
-
-        let response = self.client.get(&format!("{}/cgi-bin/luci/admin/settings/gwinfo", self.url))
-            .query(&payload)
-            .send()
+        let rand_str = Self::rand_str().await;
+        let text = self
+            .request_with_reauth(|_token| {
+                let url = format!("{}/cgi-bin/luci/admin/settings/gwinfo", self.url);
+                let payload = vec![
+                    ("get".to_string(), "part".to_string()),
+                    ("_".to_string(), rand_str.clone()),
+                ];
+                async move { self.client.get(&url).query(&payload).send().await }
+            })
             .await?;
 
-        let gw_info: GatewayInfo = response.json().await?;
-        Ok(gw_info)
+        Ok(serde_json::from_str(&text)?)
     }
 
     /// Retrieves a list of port forwarding rules from the router.
@@ -280,15 +413,16 @@ impl Tianyi {
     ///
     /// Returns an `Error` if there is a problem connecting to the router or parsing the response.
     pub async fn port_forwarding(&self) -> Result<PortForwardingData> {
-        let payload = [("_", &Self::rand_str().await)];
-
-        let response = self.client.get(&format!("{}/cgi-bin/luci/admin/settings/pmDisplay", self.url))
-            .query(&payload)
-            .send()
+        let rand_str = Self::rand_str().await;
+        let text = self
+            .request_with_reauth(|_token| {
+                let url = format!("{}/cgi-bin/luci/admin/settings/pmDisplay", self.url);
+                let payload = vec![("_".to_string(), rand_str.clone())];
+                async move { self.client.get(&url).query(&payload).send().await }
+            })
             .await?;
 
-        let port_forwarding_data: PortForwardingData = response.json().await?;
-        Ok(port_forwarding_data)
+        Ok(serde_json::from_str(&text)?)
     }
 
     pub async fn get_port_forwarding_rules(&self) -> Result<Vec<PortForwardingRule>> {
@@ -310,30 +444,37 @@ impl Tianyi {
     /// Returns an `Error` if there is a problem connecting to the router or performing 
     pub async fn set_port_forwarding_rule(&self, action: PortForwardingAction, srvname: &str, rule: Option<&PortForwardingRule>) -> Result<ActionResult> {
         let rand_str = Self::rand_str().await;
-        
-        let mut payload = vec![
-            ("srvname", srvname),
-            ("token", &self.token),
-            ("op", action.as_str()),
-            ("_", &rand_str),
-        ];
-
         let ex_port = rule.map_or("".to_owned(), |rule| rule.ex_port.to_string());
         let in_port = rule.map_or("".to_owned(), |rule| rule.in_port.to_string());
 
-        if let Some(rule) = rule {
-            payload.push(("client", &rule.client));
-            payload.push(("protocol", &rule.protocol));
-            payload.push(("exPort", &ex_port));
-            payload.push(("inPort", &in_port));
-        }
-
-        let response = self.client.post(&format!("{}/cgi-bin/luci/admin/settings/pmSetSingle", self.url))
-            .form(&payload)
-            .send()
+        let text = self
+            .request_with_reauth(|token| {
+                let url = format!("{}/cgi-bin/luci/admin/settings/pmSetSingle", self.url);
+                let mut payload = vec![
+                    ("srvname".to_string(), srvname.to_string()),
+                    ("token".to_string(), token),
+                    ("op".to_string(), action.as_str().to_string()),
+                    ("_".to_string(), rand_str.clone()),
+                ];
+
+                if let Some(rule) = rule {
+                    payload.push(("client".to_string(), rule.client.clone()));
+                    payload.push(("protocol".to_string(), rule.protocol.clone()));
+                    payload.push(("exPort".to_string(), ex_port.clone()));
+                    payload.push(("inPort".to_string(), in_port.clone()));
+                }
+
+                async move { self.client.post(&url).form(&payload).send().await }
+            })
             .await?;
 
-        let action_result: ActionResult = response.json().await?;
+        let action_result: ActionResult = serde_json::from_str(&text)?;
+        if action_result.ret_val != 0 {
+            return Err(TianyiError::RouterRejected {
+                op: action.as_str().to_string(),
+                ret_val: action_result.ret_val,
+            });
+        }
         Ok(action_result)
     }
 
@@ -0,0 +1,95 @@
+//! WAN link throughput and reconnect support.
+
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{ActionResult, Result, Tianyi, TianyiError};
+
+#[derive(Debug, Deserialize)]
+struct WanCounterSample {
+    #[serde(rename = "rxBytes")]
+    rx_bytes: u64,
+    #[serde(rename = "txBytes")]
+    tx_bytes: u64,
+}
+
+/// WAN port byte counters and the throughput computed from sampling them twice.
+#[derive(Debug, Clone, Copy)]
+pub struct WanStats {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_bps: f64,
+    pub tx_bps: f64,
+}
+
+impl Tianyi {
+    async fn wan_counters(&self) -> Result<WanCounterSample> {
+        let rand_str = Self::rand_str().await;
+        let text = self
+            .request_with_reauth(|_token| {
+                let url = format!("{}/cgi-bin/luci/admin/status/wanStatus", self.url);
+                let payload = vec![
+                    ("get".to_string(), "wan".to_string()),
+                    ("_".to_string(), rand_str.clone()),
+                ];
+                async move { self.client.get(&url).query(&payload).send().await }
+            })
+            .await?;
+
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Samples the WAN byte counters twice, `interval` apart, and computes throughput.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is a problem connecting to the router or parsing the response.
+    pub async fn wan_stats(&self, interval: Duration) -> Result<WanStats> {
+        let first = self.wan_counters().await?;
+        tokio::time::sleep(interval).await;
+        let second = self.wan_counters().await?;
+
+        let secs = interval.as_secs_f64();
+        let rx_delta = second.rx_bytes.saturating_sub(first.rx_bytes);
+        let tx_delta = second.tx_bytes.saturating_sub(first.tx_bytes);
+
+        Ok(WanStats {
+            rx_bytes: second.rx_bytes,
+            tx_bytes: second.tx_bytes,
+            rx_bps: (rx_delta as f64 * 8.0) / secs,
+            tx_bps: (tx_delta as f64 * 8.0) / secs,
+        })
+    }
+
+    /// Triggers a WAN teardown-and-redial (PPPoE/DHCP reconnect).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is a problem connecting to the router or if the router
+    /// rejects the reconnect request.
+    pub async fn reconnect_wan(&self) -> Result<ActionResult> {
+        let rand_str = Self::rand_str().await;
+
+        let text = self
+            .request_with_reauth(|token| {
+                let url = format!("{}/cgi-bin/luci/admin/settings/wanAction", self.url);
+                let payload = vec![
+                    ("token".to_string(), token),
+                    ("op".to_string(), "reconnect".to_string()),
+                    ("_".to_string(), rand_str.clone()),
+                ];
+                async move { self.client.post(&url).form(&payload).send().await }
+            })
+            .await?;
+
+        let action_result: ActionResult = serde_json::from_str(&text)?;
+        if action_result.ret_val != 0 {
+            return Err(TianyiError::RouterRejected {
+                op: "reconnect_wan".to_string(),
+                ret_val: action_result.ret_val,
+            });
+        }
+        Ok(action_result)
+    }
+}